@@ -65,6 +65,7 @@ mod wavewriter;
 mod wave;
 
 pub use errors::Error;
+pub use fourcc::FourCC;
 pub use wavereader::WaveReader;
 pub use wavewriter::{WaveWriter, AudioFrameWriter};
 pub use bext::Bext;
@@ -73,4 +74,4 @@ pub use common_format::CommonFormat;
 pub use audio_frame_reader::AudioFrameReader;
 pub use cue::Cue;
 
-pub use wave::Wave;
\ No newline at end of file
+pub use wave::{Wave, ChunkInfo, Sample, SampleReader};
\ No newline at end of file
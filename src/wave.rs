@@ -5,13 +5,16 @@ use std::collections::HashMap;
 use std::io::{Read, Write, Seek, Cursor};
 use std::io::SeekFrom;
 use std::fs::File;
+use std::marker::PhantomData;
 
 use super::errors::Error;
-use super::fmt::WaveFmt;
+use super::fmt::{WaveFmt, ADMAudioID};
 use super::bext::Bext;
 use super::chunks::ReadBWaveChunks;
-use super::fourcc::{FourCC, ReadFourCC, RIFF_SIG, RF64_SIG, BW64_SIG, WAVE_SIG, 
-    LIST_SIG,
+use super::common_format::CommonFormat;
+use super::cue::Cue;
+use super::fourcc::{FourCC, ReadFourCC, RIFF_SIG, RF64_SIG, BW64_SIG, WAVE_SIG,
+    LIST_SIG, ADTL_SIG, CUE_SIG, LABL_SIG, NOTE_SIG, LTXT_SIG, CHNA_SIG,
     DS64_SIG, FMT__SIG, DATA_SIG, BEXT_SIG, IXML_SIG, AXML_SIG};
 
 use byteorder::LittleEndian;
@@ -22,6 +25,22 @@ pub struct Wave<T: Seek> {
     inner : T
 }
 
+/// The identity, location and true length of one chunk within a RIFF/RF64
+/// form, as returned by [`Wave::chunks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkInfo {
+    /// The four-character code identifying this chunk.
+    pub ident: FourCC,
+
+    /// Byte offset of the start of this chunk's data, from the beginning
+    /// of the file.
+    pub data_offset: u64,
+
+    /// Length of this chunk's data, in bytes, resolved through the `ds64`
+    /// table if the chunk header uses a `0xFFFFFFFF` placeholder.
+    pub length: u64,
+}
+
 impl<T: Seek> Wave<T> {
 
     pub fn new(inner : T) -> Self {
@@ -209,7 +228,7 @@ impl<T> Wave<T> where T:Read + Seek {
     /// assert_eq!(chans[4].speaker, ChannelMask::BackLeft);
     /// ```
     pub fn channels(&mut self) -> Result<Vec<ChannelDescriptor>, Error> {
-        
+
         let format = self.format()?;
         let channel_masks : Vec<ChannelMask> = match (format.channel_count, format.extended_format) {
             (1,_) => vec![ChannelMask::FrontCenter],
@@ -218,9 +237,56 @@ impl<T> Wave<T> where T:Read + Seek {
             (n,_) => vec![ChannelMask::DirectOut; n as usize]
         };
 
-        Ok( (0..format.channel_count).zip(channel_masks)
+        let mut descriptors : Vec<ChannelDescriptor> = (0..format.channel_count).zip(channel_masks)
             .map(|(i,m)| ChannelDescriptor { index: i, speaker:m, adm_track_audio_ids: vec![] } )
-            .collect() )
+            .collect();
+
+        if let Ok(chna) = self.read_chna_records() {
+            for (track_index, audio_id) in chna {
+                if track_index == 0 { continue }
+                if let Some(descriptor) = descriptors.get_mut((track_index - 1) as usize) {
+                    descriptor.adm_track_audio_ids.push(audio_id);
+                }
+            }
+        }
+
+        Ok(descriptors)
+    }
+
+    /// Parse the `chna` chunk's ADM channel-allocation table into
+    /// `(track_index, ADMAudioID)` pairs, `track_index` being 1-based.
+    ///
+    /// Returns `Err` if the file has no `chna` chunk, which `channels()`
+    /// treats as "no ADM track associations" rather than a hard failure.
+    fn read_chna_records(&mut self) -> Result<Vec<(u16, ADMAudioID)>, Error> {
+        let _ = self.seek_chunk(CHNA_SIG, 0)?;
+        let _track_count = self.inner.read_u16::<LittleEndian>()?;
+        let uid_count = self.inner.read_u16::<LittleEndian>()?;
+
+        let mut records = Vec::with_capacity(uid_count as usize);
+        for _ in 0..uid_count {
+            let record_start = self.inner.seek( SeekFrom::Current(0) )?;
+
+            let track_index = self.inner.read_u16::<LittleEndian>()?;
+            let audio_track_uid = self.read_padded_ascii(12)?;
+            let audio_track_format_id = self.read_padded_ascii(14)?;
+            let audio_pack_format_id = self.read_padded_ascii(12)?;
+
+            records.push( (track_index, ADMAudioID { audio_track_uid, audio_track_format_id, audio_pack_format_id }) );
+
+            self.inner.seek( SeekFrom::Start(record_start + 40) )?;
+        }
+
+        Ok(records)
+    }
+
+    /// Read `len` bytes as space/NUL-padded ASCII and trim the padding.
+    fn read_padded_ascii(&mut self, len: usize) -> Result<String, Error> {
+        let mut buffer = vec![0u8; len];
+        self.inner.read_exact(&mut buffer)?;
+
+        let text = String::from_utf8_lossy(&buffer);
+        Ok( text.trim_end_matches(|c| c == ' ' || c == '\u{0}').to_string() )
     }
 
     /// Read iXML metadata
@@ -230,6 +296,387 @@ impl<T> Wave<T> where T:Read + Seek {
 
     /// Read axml metadata
     pub fn read_axml(&mut self, buffer: &mut Vec<u8>) -> Result<usize, Error> {
-        self.read_chunk(AXML_SIG, 0, buffer) 
+        self.read_chunk(AXML_SIG, 0, buffer)
+    }
+
+    /// Read cue points from the `cue ` chunk, labeled with any matching
+    /// `labl`/`note`/`ltxt` sub-chunks of the `LIST` chunk of form `adtl`.
+    ///
+    /// Cue points without a matching annotation are returned with `label`,
+    /// `note` and `labeled_text_length` left unset.
+    pub fn cue_points(&mut self) -> Result<Vec<Cue>, Error> {
+        let mut cues = self.read_cue_chunk()?;
+
+        if let Ok(annotations) = self.read_adtl_annotations() {
+            for cue in cues.iter_mut() {
+                if let Some((label, note, labeled_text_length)) = annotations.get(&cue.id) {
+                    cue.label = label.clone();
+                    cue.note = note.clone();
+                    cue.labeled_text_length = *labeled_text_length;
+                }
+            }
+        }
+
+        Ok(cues)
+    }
+
+    /// Parse the `cue ` chunk into unlabeled `Cue` records.
+    fn read_cue_chunk(&mut self) -> Result<Vec<Cue>, Error> {
+        let _ = self.seek_chunk(CUE_SIG, 0)?;
+        let count = self.inner.read_u32::<LittleEndian>()?;
+
+        let mut cues = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let id = self.inner.read_u32::<LittleEndian>()?;
+            let position = self.inner.read_u32::<LittleEndian>()?;
+            let data_chunk_id = self.inner.read_fourcc()?;
+            let chunk_start = self.inner.read_u32::<LittleEndian>()?;
+            let block_start = self.inner.read_u32::<LittleEndian>()?;
+            let sample_offset = self.inner.read_u32::<LittleEndian>()?;
+
+            cues.push( Cue {
+                id, position, data_chunk_id, chunk_start, block_start, sample_offset,
+                label: None, note: None, labeled_text_length: None
+            });
+        }
+
+        Ok(cues)
+    }
+
+    /// Scan the `LIST` chunk of form type `adtl` for `labl`, `note` and
+    /// `ltxt` sub-chunks, keyed by cue ID.
+    fn read_adtl_annotations(&mut self) -> Result<HashMap<u32, (Option<String>, Option<String>, Option<u32>)>, Error> {
+        let mut index = 0;
+        let content_length = loop {
+            let length = self.seek_chunk(LIST_SIG, index)?;
+            let form = self.inner.read_fourcc()?;
+            if form == ADTL_SIG { break length - 4; }
+            index += 1;
+        };
+
+        let mut remain = content_length;
+        let mut annotations: HashMap<u32, (Option<String>, Option<String>, Option<u32>)> = HashMap::new();
+
+        while remain > 0 {
+            let (ident, length, displacement) = self.read_chunk_header_immediate()?;
+            let content_start = self.inner.seek( SeekFrom::Current(0) )?;
+            let id = self.inner.read_u32::<LittleEndian>()?;
+
+            match ident {
+                LABL_SIG | NOTE_SIG => {
+                    let mut text = vec![0u8; (length - 4) as usize];
+                    self.inner.read_exact(&mut text)?;
+                    let text = String::from_utf8_lossy(&text).trim_end_matches('\u{0}').to_string();
+
+                    let record = annotations.entry(id).or_default();
+                    if ident == LABL_SIG { record.0 = Some(text); } else { record.1 = Some(text); }
+                },
+                LTXT_SIG => {
+                    let sample_length = self.inner.read_u32::<LittleEndian>()?;
+                    annotations.entry(id).or_default().2 = Some(sample_length);
+                },
+                _ => {},
+            }
+
+            self.inner.seek( SeekFrom::Start(content_start + displacement) )?;
+            remain = remain - (8 + displacement);
+        }
+
+        Ok(annotations)
+    }
+
+    /// Enumerate every chunk in the file's RIFF/RF64 form, including chunks
+    /// this crate otherwise has no dedicated accessor for.
+    ///
+    /// `ChunkInfo::length` is resolved through the `ds64` table for files
+    /// using `0xFFFFFFFF` placeholder lengths, so it is always the true
+    /// 64-bit chunk length. Read a chunk's payload with
+    /// [`Wave::read_chunk_by_fourcc`].
+    pub fn chunks(&mut self) -> Result<Vec<ChunkInfo>, Error> {
+        self.inner.seek( SeekFrom::Start(0) )?;
+        let (_, form_length, _) = self.read_chunk_header_immediate()?;
+        let _ = self.inner.read_fourcc()?;
+
+        let mut remain = form_length - 4;
+        let mut retval = Vec::new();
+
+        while remain > 0 {
+            let (ident, length, displacement) = self.read_chunk_header_immediate()?;
+            let data_offset = self.inner.seek( SeekFrom::Current(0) )?;
+
+            retval.push( ChunkInfo { ident, data_offset, length } );
+
+            self.inner.seek( SeekFrom::Current(displacement as i64) )?;
+            remain = remain - (8 + displacement);
+        }
+
+        Ok(retval)
+    }
+
+    /// Read the payload of a chunk located via [`Wave::chunks`], identified
+    /// by its FourCC and zero-based occurrence `index` among chunks sharing
+    /// that FourCC.
+    pub fn read_chunk_by_fourcc(&mut self, ident: FourCC, index: u32, buffer: &mut [u8]) -> Result<usize, Error> {
+        self.read_chunk(ident, index, buffer)
+    }
+
+    /// Create a reader of decoded, interleaved audio samples.
+    ///
+    /// `S` is the sample type each channel value is decoded into, one of
+    /// `i16`, `i32` or `f32`. The returned [`SampleReader`] starts at the
+    /// first frame of the `data` chunk.
+    ///
+    /// ```
+    /// use bwavfile::Wave;
+    ///
+    /// let mut w = Wave::open("tests/media/ff_silence.wav").unwrap();
+    /// let mut samples = w.samples::<i16>().unwrap();
+    /// let frame = samples.read_frame().unwrap().unwrap();
+    /// assert_eq!(frame.len(), w.format().unwrap().channel_count as usize);
+    /// ```
+    pub fn samples<S: Sample>(&mut self) -> Result<SampleReader<'_, T, S>, Error> {
+        let format = self.format()?;
+        let data_length = self.seek_chunk(DATA_SIG, 0)?;
+        let data_start = self.inner.seek( SeekFrom::Current(0) )?;
+        let frame_length = data_length / (format.block_alignment as u64);
+
+        Ok( SampleReader {
+            inner: &mut self.inner, format, data_start, frame_length, frame_position: 0,
+            _marker: PhantomData
+        })
+    }
+}
+
+/// A sample value a [`SampleReader`] can decode PCM frames into.
+///
+/// Implemented for `i16`, `i32` and `f32`, which cover the integer and
+/// floating-point sample representations used by professional audio
+/// applications.
+pub trait Sample: Copy {
+    /// Construct a sample from an integer PCM value of the given native
+    /// `bits_per_sample`, already sign-extended to `i32`.
+    fn from_pcm_integer(value: i32, bits_per_sample: u16) -> Self;
+
+    /// Construct a sample directly from an IEEE float PCM value.
+    fn from_pcm_float(value: f32) -> Self;
+}
+
+impl Sample for i16 {
+    fn from_pcm_integer(value: i32, bits_per_sample: u16) -> Self {
+        let shift = 32 - bits_per_sample as i32;
+        ((value << shift) >> 16) as i16
+    }
+
+    fn from_pcm_float(value: f32) -> Self {
+        (value.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+    }
+}
+
+impl Sample for i32 {
+    fn from_pcm_integer(value: i32, bits_per_sample: u16) -> Self {
+        value << (32 - bits_per_sample as i32)
+    }
+
+    fn from_pcm_float(value: f32) -> Self {
+        (value.clamp(-1.0, 1.0) as f64 * i32::MAX as f64) as i32
+    }
+}
+
+impl Sample for f32 {
+    fn from_pcm_integer(value: i32, bits_per_sample: u16) -> Self {
+        value as f32 / (1i64 << (bits_per_sample - 1)) as f32
+    }
+
+    fn from_pcm_float(value: f32) -> Self {
+        value
+    }
+}
+
+/// Decode a single PCM sample, sign-extending integer formats to `i32`.
+///
+/// `bits_per_sample` must be 8, 16, 24 or 32, the only integer PCM widths
+/// this crate knows how to decode; 8-bit samples are unsigned with a 128
+/// bias, wider samples are signed two's-complement and are sign-extended
+/// by shifting the sign bit at `bits_per_sample - 1` up to bit 31 and back
+/// down arithmetically. `raw` must hold exactly `bits_per_sample / 8`
+/// bytes.
+///
+/// Returns `Err` for a sample width this crate cannot decode, rather than
+/// panicking on a file-supplied `bits_per_sample`.
+fn decode_pcm_integer(raw: &[u8], bits_per_sample: u16) -> Result<i32, Error> {
+    if !matches!(bits_per_sample, 8 | 16 | 24 | 32) || raw.len() != (bits_per_sample / 8) as usize {
+        return Err( std::io::Error::new(std::io::ErrorKind::InvalidData,
+            format!("unsupported PCM sample width: {} bits", bits_per_sample)).into() );
+    }
+
+    if bits_per_sample == 8 {
+        return Ok( raw[0] as i32 - 128 );
+    }
+
+    let mut unsigned: u32 = 0;
+    for (i, byte) in raw.iter().enumerate() {
+        unsigned |= (*byte as u32) << (8 * i);
+    }
+
+    let shift = 32 - bits_per_sample as u32;
+    Ok( ((unsigned << shift) as i32) >> shift )
+}
+
+#[cfg(test)]
+mod decode_tests {
+    use super::*;
+
+    #[test]
+    fn eight_bit_unsigned_bias() {
+        assert_eq!(decode_pcm_integer(&[128], 8).unwrap(), 0);
+        assert_eq!(decode_pcm_integer(&[255], 8).unwrap(), 127);
+        assert_eq!(decode_pcm_integer(&[0], 8).unwrap(), -128);
+    }
+
+    #[test]
+    fn sixteen_bit_sign_extension() {
+        assert_eq!(decode_pcm_integer(&[0x00, 0x80], 16).unwrap(), i16::MIN as i32);
+        assert_eq!(decode_pcm_integer(&[0xff, 0x7f], 16).unwrap(), i16::MAX as i32);
+    }
+
+    #[test]
+    fn twenty_four_bit_sign_extension() {
+        // 0x800000 is the most negative 24-bit two's-complement value.
+        assert_eq!(decode_pcm_integer(&[0x00, 0x00, 0x80], 24).unwrap(), -0x0080_0000);
+        assert_eq!(decode_pcm_integer(&[0xff, 0xff, 0x7f], 24).unwrap(), 0x007f_ffff);
+    }
+
+    #[test]
+    fn thirty_two_bit_passthrough() {
+        assert_eq!(decode_pcm_integer(&[0, 0, 0, 0x80], 32).unwrap(), i32::MIN);
+    }
+
+    #[test]
+    fn rejects_unsupported_bit_depths() {
+        assert!(decode_pcm_integer(&[0, 0, 0, 0], 20).is_err());
+        assert!(decode_pcm_integer(&[0, 0, 0, 0], 33).is_err());
+        assert!(decode_pcm_integer(&[0, 0, 0, 0], 39).is_err());
+    }
+
+    #[test]
+    fn sample_trait_float_passthrough() {
+        assert_eq!(f32::from_pcm_float(0.25), 0.25);
+        assert_eq!(i16::from_pcm_float(1.0), i16::MAX);
+    }
+}
+
+/// Decode one interleaved frame of `format.block_alignment` raw bytes into
+/// `format.channel_count` samples of type `S`.
+fn decode_frame<S: Sample>(raw: &[u8], format: &WaveFmt) -> Result<Vec<S>, Error> {
+    let bytes_per_sample = (format.bits_per_sample / 8) as usize;
+    let is_float = format.tag == CommonFormat::IEEEFloat && format.bits_per_sample == 32;
+
+    raw.chunks_exact(bytes_per_sample)
+        .take(format.channel_count as usize)
+        .map(|sample| if is_float {
+            Ok( S::from_pcm_float(f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]])) )
+        } else {
+            Ok( S::from_pcm_integer(decode_pcm_integer(sample, format.bits_per_sample)?, format.bits_per_sample) )
+        })
+        .collect()
+}
+
+/// Reads decoded, interleaved audio frames from a [`Wave`]'s `data` chunk.
+///
+/// Created by [`Wave::samples`].
+pub struct SampleReader<'w, T: Read + Seek, S: Sample> {
+    inner: &'w mut T,
+    format: WaveFmt,
+    data_start: u64,
+    frame_length: u64,
+    frame_position: u64,
+    _marker: PhantomData<S>,
+}
+
+impl<'w, T: Read + Seek, S: Sample> SampleReader<'w, T, S> {
+    /// Read and decode the next interleaved frame.
+    ///
+    /// Returns `Ok(None)` once the end of the `data` chunk is reached.
+    pub fn read_frame(&mut self) -> Result<Option<Vec<S>>, Error> {
+        if self.frame_position >= self.frame_length {
+            return Ok(None);
+        }
+
+        let mut raw = vec![0u8; self.format.block_alignment as usize];
+
+        match self.inner.read_exact(&mut raw) {
+            Ok(()) => {
+                self.frame_position += 1;
+                Ok( Some(decode_frame(&raw, &self.format)?) )
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Seek to frame `frame` of the `data` chunk; the next [`Self::read_frame`]
+    /// call reads from that position.
+    ///
+    /// Returns `Err` if `frame` is past the end of the data, per
+    /// [`Wave::frame_length`].
+    pub fn seek_to_frame(&mut self, frame: u64) -> Result<(), Error> {
+        if frame > self.frame_length {
+            return Err( std::io::Error::new(std::io::ErrorKind::UnexpectedEof,
+                format!("frame {} is past end of data ({} frames)", frame, self.frame_length)).into() );
+        }
+
+        let offset = self.data_start + frame * (self.format.block_alignment as u64);
+        self.inner.seek( SeekFrom::Start(offset) )?;
+        self.frame_position = frame;
+
+        Ok(())
+    }
+
+    /// The frame position the next [`Self::read_frame`] call will read from.
+    pub fn frame_position(&self) -> u64 {
+        self.frame_position
+    }
+
+    /// Read a contiguous range of frames de-interleaved into one buffer per
+    /// channel, scattering sample `c` of frame `i` into `output[c][i]`.
+    ///
+    /// `output` must contain exactly one buffer per channel, all of equal
+    /// length; that length is the number of frames requested. Returns the
+    /// number of frames actually produced, which is less than requested at
+    /// the end of the `data` chunk.
+    pub fn read_planar(&mut self, output: &mut [&mut [S]]) -> Result<usize, Error> {
+        let channel_count = self.format.channel_count as usize;
+        assert_eq!(output.len(), channel_count, "one buffer is required per channel");
+
+        let block_alignment = self.format.block_alignment as usize;
+        let frames_remaining = self.frame_length.saturating_sub(self.frame_position) as usize;
+        let frame_count = output.iter().map(|channel| channel.len()).min().unwrap_or(0)
+            .min(frames_remaining);
+
+        let mut raw = vec![0u8; frame_count * block_alignment];
+        let bytes_read = self.read_up_to(&mut raw)?;
+        let frames_read = bytes_read / block_alignment;
+
+        for (i, frame) in raw[..frames_read * block_alignment].chunks_exact(block_alignment).enumerate() {
+            for (c, sample) in decode_frame::<S>(frame, &self.format)?.into_iter().enumerate() {
+                output[c][i] = sample;
+            }
+        }
+
+        self.frame_position += frames_read as u64;
+        Ok(frames_read)
+    }
+
+    /// Fill `buffer` as far as possible before end-of-data, returning the
+    /// number of bytes actually read.
+    fn read_up_to(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let mut total = 0;
+        while total < buffer.len() {
+            match self.inner.read(&mut buffer[total..])? {
+                0 => break,
+                n => total += n,
+            }
+        }
+        Ok(total)
     }
 }
\ No newline at end of file